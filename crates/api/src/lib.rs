@@ -0,0 +1,165 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use auth::{KeyStore, Scope};
+use axum::{
+    extract::{Path, Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use storage::{SpanEvent, SpanFilter, SpanStore};
+use tokio::sync::{broadcast::error::RecvError, RwLock};
+use trace::{Span, SpanId, TraceId};
+
+/// Read side of the storage backend, shared with the proxy listener so both
+/// see the same spans. `RwLock` rather than a `Mutex` since reads (every
+/// route here except the two deletes) vastly outnumber writes.
+pub type SharedStore = Arc<RwLock<dyn SpanStore>>;
+
+#[derive(Clone)]
+struct ApiState {
+    store: SharedStore,
+}
+
+/// Query-string shape for `GET /spans` and `GET /events`; mirrors
+/// `storage::SpanFilter` field-for-field so both routes agree on what a
+/// given set of params means.
+#[derive(Debug, Deserialize)]
+struct FilterParams {
+    model: Option<String>,
+    status: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    name_contains: Option<String>,
+}
+
+impl From<FilterParams> for SpanFilter {
+    fn from(params: FilterParams) -> Self {
+        SpanFilter {
+            model: params.model,
+            status: params.status,
+            since: params.since,
+            until: params.until,
+            name_contains: params.name_contains,
+        }
+    }
+}
+
+async fn list_traces(State(state): State<ApiState>) -> Json<Vec<TraceId>> {
+    Json(state.store.read().await.trace_ids())
+}
+
+async fn trace_spans(State(state): State<ApiState>, Path(trace_id): Path<TraceId>) -> Json<Vec<SpanId>> {
+    Json(state.store.read().await.spans_for_trace(trace_id))
+}
+
+async fn list_spans(State(state): State<ApiState>, Query(params): Query<FilterParams>) -> Json<Vec<Span>> {
+    let filter: SpanFilter = params.into();
+    Json(state.store.read().await.filter_spans(&filter))
+}
+
+async fn delete_trace(State(state): State<ApiState>, Path(trace_id): Path<TraceId>) -> Json<usize> {
+    Json(state.store.write().await.delete_trace(trace_id))
+}
+
+async fn delete_span(State(state): State<ApiState>, Path(span_id): Path<SpanId>) -> Json<bool> {
+    Json(state.store.write().await.delete_span(span_id))
+}
+
+/// One schema for every message on the `/events` stream, so a consumer can
+/// always dispatch on `type` instead of special-casing the lagged case.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum EventPayload<'a> {
+    Created { span: &'a Span },
+    Completed { span: &'a Span },
+    Failed { span: &'a Span },
+    Lagged { skipped: u64 },
+}
+
+/// Live-tail span lifecycle events as server-sent events, filtered the same
+/// way `GET /spans` is. A subscriber that falls more than
+/// `storage::EVENT_CHANNEL_CAPACITY` events behind the newest one sees a
+/// single `Lagged` event instead of silently missing the gap.
+async fn events(
+    State(state): State<ApiState>,
+    Query(params): Query<FilterParams>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let filter: SpanFilter = params.into();
+    let store = state.store.clone();
+    let mut rx = store.read().await.subscribe();
+
+    let stream = async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let span_id = match &event {
+                        SpanEvent::Created(id) | SpanEvent::Completed(id) | SpanEvent::Failed(id, _) => *id,
+                    };
+                    let Some(span) = store.read().await.get(span_id) else {
+                        continue;
+                    };
+                    if !storage::matches_filter(&span, &filter) {
+                        continue;
+                    }
+                    let payload = match &event {
+                        SpanEvent::Created(_) => EventPayload::Created { span: &span },
+                        SpanEvent::Completed(_) => EventPayload::Completed { span: &span },
+                        SpanEvent::Failed(_, _) => EventPayload::Failed { span: &span },
+                    };
+                    if let Ok(sse_event) = Event::default().json_data(&payload) {
+                        yield Ok(sse_event);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    if let Ok(sse_event) = Event::default().json_data(&EventPayload::Lagged { skipped }) {
+                        yield Ok(sse_event);
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
+
+pub fn router(store: SharedStore, key_store: Option<Arc<KeyStore>>) -> Router {
+    let state = ApiState { store };
+
+    let reads = Router::new()
+        .route("/traces", get(list_traces))
+        .route("/traces/{trace_id}/spans", get(trace_spans))
+        .route("/spans", get(list_spans))
+        .route("/events", get(events))
+        .with_state(state.clone());
+
+    let deletes = Router::new()
+        .route("/traces/{trace_id}", delete(delete_trace))
+        .route("/spans/{span_id}", delete(delete_span))
+        .with_state(state);
+
+    let (reads, deletes) = match key_store {
+        Some(key_store) => (
+            auth::require_scope(reads, key_store.clone(), Scope::Query),
+            auth::require_scope(deletes, key_store, Scope::Admin),
+        ),
+        None => (reads, deletes),
+    };
+
+    reads.merge(deletes)
+}
+
+pub async fn serve(store: SharedStore, addr: &str, key_store: Option<Arc<KeyStore>>) -> std::io::Result<()> {
+    let app = router(store, key_store);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("api listening on {}", addr);
+    axum::serve(listener, app)
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
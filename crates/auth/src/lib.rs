@@ -0,0 +1,294 @@
+use std::sync::Arc;
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::State,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+pub type KeyId = Uuid;
+
+/// What a key is allowed to do. `/api/generate` and `/api/chat` require
+/// `Proxy`; read endpoints require `Query`; deletes require `Admin`. Key
+/// management (`admin_router`) is gated on the admin secret, not on a scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Proxy,
+    Query,
+    Admin,
+}
+
+#[derive(Debug, Clone)]
+struct ApiKey {
+    id: KeyId,
+    hash: String,
+    scopes: Vec<Scope>,
+    expires_at: Option<DateTime<Utc>>,
+    revoked: bool,
+}
+
+impl ApiKey {
+    fn is_live(&self) -> bool {
+        if self.revoked {
+            return false;
+        }
+        match self.expires_at {
+            Some(expires_at) => Utc::now() < expires_at,
+            None => true,
+        }
+    }
+}
+
+/// Holds minted API keys. Secrets are never stored, only their Argon2 hash,
+/// so a copy of the store (or whatever eventually backs it) can't be used to
+/// impersonate a caller.
+#[derive(Default)]
+pub struct KeyStore {
+    keys: RwLock<Vec<ApiKey>>,
+}
+
+impl KeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mint a new key with the given scopes and optional expiry. The
+    /// plaintext secret is returned here and only here; everything stored
+    /// from this point on is its hash.
+    pub async fn mint(&self, scopes: Vec<Scope>, expires_at: Option<DateTime<Utc>>) -> (KeyId, String) {
+        let id = Uuid::new_v4();
+        let secret = generate_secret();
+        let hash = hash_secret(&secret);
+
+        self.keys.write().await.push(ApiKey {
+            id,
+            hash,
+            scopes,
+            expires_at,
+            revoked: false,
+        });
+
+        (id, secret)
+    }
+
+    pub async fn revoke(&self, id: KeyId) -> bool {
+        let mut keys = self.keys.write().await;
+        match keys.iter_mut().find(|k| k.id == id) {
+            Some(key) => {
+                key.revoked = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Verify a bearer token against every live key carrying `scope`, in
+    /// constant time per candidate, and return the matching key's id.
+    async fn authorize(&self, token: &str, scope: Scope) -> Option<KeyId> {
+        let keys = self.keys.read().await;
+        keys.iter()
+            .filter(|key| key.is_live() && key.scopes.contains(&scope))
+            .find(|key| verify_secret(token, &key.hash))
+            .map(|key| key.id)
+    }
+}
+
+/// Generate a random secret suitable for an API key or an admin bootstrap
+/// token. Plaintext only ever lives long enough to be hashed or handed back
+/// to the caller that requested it.
+pub fn generate_secret() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+fn hash_secret(secret: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(secret.as_bytes(), &salt)
+        .expect("hash api key secret")
+        .to_string()
+}
+
+fn verify_secret(secret: &str, hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return false;
+    };
+    Argon2::default()
+        .verify_password(secret.as_bytes(), &parsed)
+        .is_ok()
+}
+
+#[derive(Clone)]
+struct AuthState {
+    key_store: Arc<KeyStore>,
+    scope: Scope,
+}
+
+/// Wrap `router` so every request must carry a bearer token authorized for
+/// `scope`.
+pub fn require_scope(router: Router, key_store: Arc<KeyStore>, scope: Scope) -> Router {
+    router.layer(axum::middleware::from_fn_with_state(
+        AuthState { key_store, scope },
+        check_scope,
+    ))
+}
+
+async fn check_scope(
+    State(state): State<AuthState>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let Some(token) = token else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    match state.key_store.authorize(token, state.scope).await {
+        Some(_) => Ok(next.run(req).await),
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+#[derive(Clone)]
+struct AdminAuthState {
+    secret_hash: String,
+}
+
+/// Require a bearer token matching `admin_secret` on every request. Binding
+/// the admin listener off loopback for a shared deployment is only as safe
+/// as the network it's reachable from; this gives mint/revoke a check of
+/// their own so another local user or process on the same host can't
+/// self-mint an `Admin` key just by reaching the port.
+async fn check_admin_secret(
+    State(state): State<AdminAuthState>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if verify_secret(token, &state.secret_hash) => Ok(next.run(req).await),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MintRequest {
+    scopes: Vec<Scope>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct MintResponse {
+    id: KeyId,
+    secret: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RevokeResponse {
+    revoked: bool,
+}
+
+async fn mint_key(
+    State(key_store): State<Arc<KeyStore>>,
+    Json(req): Json<MintRequest>,
+) -> impl IntoResponse {
+    let (id, secret) = key_store.mint(req.scopes, req.expires_at).await;
+    Json(MintResponse { id, secret })
+}
+
+async fn revoke_key(
+    State(key_store): State<Arc<KeyStore>>,
+    axum::extract::Path(id): axum::extract::Path<KeyId>,
+) -> impl IntoResponse {
+    let revoked = key_store.revoke(id).await;
+    Json(RevokeResponse { revoked })
+}
+
+/// Routes for minting and revoking keys, gated on a bearer token matching
+/// `admin_secret`. Binding this on a trusted address is still good practice,
+/// but `admin_secret` means reaching the port isn't enough on its own to
+/// mint an `Admin` key.
+pub fn admin_router(key_store: Arc<KeyStore>, admin_secret: &str) -> Router {
+    let admin_auth = AdminAuthState {
+        secret_hash: hash_secret(admin_secret),
+    };
+    Router::new()
+        .route("/keys", post(mint_key))
+        .route("/keys/{id}/revoke", post(revoke_key))
+        .with_state(key_store)
+        .layer(axum::middleware::from_fn_with_state(
+            admin_auth,
+            check_admin_secret,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mint_and_authorize_round_trip() {
+        let store = KeyStore::new();
+        let (_id, secret) = store.mint(vec![Scope::Proxy], None).await;
+        assert!(store.authorize(&secret, Scope::Proxy).await.is_some());
+        assert!(store.authorize(&secret, Scope::Admin).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn revoked_key_is_rejected() {
+        let store = KeyStore::new();
+        let (id, secret) = store.mint(vec![Scope::Query], None).await;
+        assert!(store.revoke(id).await);
+        assert!(store.authorize(&secret, Scope::Query).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_key_is_rejected() {
+        let store = KeyStore::new();
+        let (_id, secret) = store
+            .mint(vec![Scope::Query], Some(Utc::now() - chrono::Duration::seconds(1)))
+            .await;
+        assert!(store.authorize(&secret, Scope::Query).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn wrong_secret_is_rejected() {
+        let store = KeyStore::new();
+        let (_id, _secret) = store.mint(vec![Scope::Proxy], None).await;
+        assert!(store.authorize("not-the-secret", Scope::Proxy).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn revoking_unknown_id_returns_false() {
+        let store = KeyStore::new();
+        assert!(!store.revoke(Uuid::new_v4()).await);
+    }
+
+    #[test]
+    fn verify_secret_matches_only_the_original_plaintext() {
+        let hash = hash_secret("correct-horse-battery-staple");
+        assert!(verify_secret("correct-horse-battery-staple", &hash));
+        assert!(!verify_secret("wrong", &hash));
+    }
+}
@@ -5,9 +5,9 @@ use tokio::sync::RwLock;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
-use storage::SpanStore;
+use storage::{MemStore, SpanStore, SqliteStore};
 
-pub type SharedStore = Arc<RwLock<SpanStore>>;
+pub type SharedStore = Arc<RwLock<dyn SpanStore>>;
 
 #[derive(Parser, Debug)]
 #[command(name = "llmtrace", about = "LLM trace daemon with Ollama proxy")]
@@ -23,6 +23,37 @@ struct Args {
     /// Ollama server URL
     #[arg(long, default_value = "http://localhost:11434")]
     ollama_url: String,
+
+    /// Path to a SQLite database file for persistent span storage.
+    /// Spans are kept in memory only when this is omitted.
+    #[arg(long)]
+    db: Option<String>,
+
+    /// OTLP collector endpoint (e.g. http://localhost:4318) to export
+    /// completed/failed spans to. Export is disabled when this is omitted.
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+
+    /// Require a scoped API key (bearer token) on the proxy and api
+    /// listeners. Off by default, which is fine for a single local user but
+    /// not for a shared deployment.
+    #[arg(long)]
+    auth: bool,
+
+    /// Address the key-management admin routes (mint/revoke) listen on when
+    /// --auth is set. Keep this off any address a caller can already reach
+    /// with just a Proxy/Query key.
+    #[arg(long, default_value = "127.0.0.1:3002")]
+    admin_addr: String,
+
+    /// Pre-shared secret required to mint/revoke keys via the admin
+    /// listener, checked in addition to wherever --admin-addr is bound. If
+    /// --auth is set and this is omitted, a random secret is generated and
+    /// logged once at startup — set this explicitly for anything other than
+    /// local testing, since network reachability alone is not enough to
+    /// keep another local user or process from self-minting an Admin key.
+    #[arg(long, env = "LLMTRACE_ADMIN_SECRET")]
+    admin_secret: Option<String>,
 }
 
 #[tokio::main]
@@ -36,23 +67,74 @@ async fn main() {
 
     info!("LLM trace daemon starting");
 
-    let store: SharedStore = Arc::new(RwLock::new(SpanStore::new()));
+    let store: SharedStore = match &args.db {
+        Some(path) => {
+            info!("using sqlite store at {}", path);
+            Arc::new(RwLock::new(
+                SqliteStore::open(path).expect("failed to open sqlite store"),
+            ))
+        }
+        None => Arc::new(RwLock::new(MemStore::new())),
+    };
+
+    let otlp_exporter = args.otlp_endpoint.as_ref().map(|endpoint| {
+        info!("exporting spans to otlp collector at {}", endpoint);
+        exporter::OtlpExporter::spawn(endpoint.clone())
+    });
+
+    let key_store = args.auth.then(|| Arc::new(auth::KeyStore::new()));
 
     // Start API server
     let api_store = store.clone();
     let api_addr = args.api_addr.clone();
+    let api_key_store = key_store.clone();
     let api_handle = tokio::spawn(async move {
-        if let Err(e) = api::serve(api_store, &api_addr).await {
+        if let Err(e) = api::serve(api_store, &api_addr, api_key_store).await {
             tracing::error!("api server error: {}", e);
         }
     });
 
+    let admin_handle = key_store.clone().map(|key_store| {
+        let admin_addr = args.admin_addr.clone();
+        let admin_secret = args.admin_secret.clone().unwrap_or_else(|| {
+            let generated = auth::generate_secret();
+            tracing::warn!(
+                "no --admin-secret set; generated one-time admin secret: {}",
+                generated
+            );
+            generated
+        });
+        tokio::spawn(async move {
+            let app = auth::admin_router(key_store, &admin_secret);
+            let listener = match tokio::net::TcpListener::bind(&admin_addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    tracing::error!("admin server bind error: {}", e);
+                    return;
+                }
+            };
+            info!("admin key management listening on {}", admin_addr);
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("admin server error: {}", e);
+            }
+        })
+    });
+
     // Start Proxy server
     let proxy_store = store.clone();
     let proxy_addr = args.proxy_addr.clone();
     let ollama_url = args.ollama_url.clone();
+    let proxy_key_store = key_store.clone();
     let proxy_handle = tokio::spawn(async move {
-        if let Err(e) = proxy::serve(proxy_store, &proxy_addr, &ollama_url).await {
+        if let Err(e) = proxy::serve(
+            proxy_store,
+            &proxy_addr,
+            &ollama_url,
+            otlp_exporter,
+            proxy_key_store,
+        )
+        .await
+        {
             tracing::error!("proxy server error: {}", e);
         }
     });
@@ -67,4 +149,7 @@ async fn main() {
 
     api_handle.abort();
     proxy_handle.abort();
+    if let Some(admin_handle) = admin_handle {
+        admin_handle.abort();
+    }
 }
@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{any_value::Value as OtlpValue, AnyValue, KeyValue};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use opentelemetry_proto::tonic::trace::v1::{
+    span::SpanKind, status::StatusCode, ResourceSpans, ScopeSpans, Span as OtlpSpan, Status,
+};
+use prost::Message;
+use tokio::sync::mpsc;
+use trace::{Span, SpanStatus};
+
+const EXPORT_QUEUE_CAPACITY: usize = 1024;
+const MAX_RETRIES: u32 = 3;
+
+/// Ships completed/failed spans to an OTLP collector (Jaeger, Tempo, Grafana, ...)
+/// over HTTP/protobuf.
+///
+/// Exporting is incremental: the proxy pushes a span onto a bounded queue the
+/// moment it finishes, and a background task sends them one at a time with
+/// retry on transient failures so the request path never blocks on the
+/// network call.
+#[derive(Clone)]
+pub struct OtlpExporter {
+    tx: mpsc::Sender<Span>,
+}
+
+impl OtlpExporter {
+    /// Spawn the background export task and return a handle to queue spans on.
+    pub fn spawn(endpoint: String) -> Self {
+        let (tx, rx) = mpsc::channel(EXPORT_QUEUE_CAPACITY);
+        tokio::spawn(run(endpoint, rx));
+        Self { tx }
+    }
+
+    /// Queue a finished span for export. If the in-flight queue is full the
+    /// span is dropped and a warning logged, rather than applying
+    /// backpressure to the caller.
+    pub fn export(&self, span: Span) {
+        if let Err(e) = self.tx.try_send(span) {
+            tracing::warn!("otlp export queue full, dropping span: {}", e);
+        }
+    }
+}
+
+async fn run(endpoint: String, mut rx: mpsc::Receiver<Span>) {
+    let client = reqwest::Client::new();
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+
+    while let Some(span) = rx.recv().await {
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![to_resource_spans(span)],
+        };
+        send_with_retry(&client, &url, request.encode_to_vec()).await;
+    }
+}
+
+async fn send_with_retry(client: &reqwest::Client, url: &str, body: Vec<u8>) {
+    let mut attempt = 0;
+    loop {
+        let result = client
+            .post(url)
+            .header("content-type", "application/x-protobuf")
+            .body(body.clone())
+            .send()
+            .await;
+
+        match result {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) if resp.status().is_client_error() => {
+                tracing::warn!("otlp collector rejected export ({}), dropping span", resp.status());
+                return;
+            }
+            _ if attempt >= MAX_RETRIES => {
+                tracing::warn!("otlp export failed after {} attempts, dropping span", attempt + 1);
+                return;
+            }
+            Ok(resp) => {
+                tracing::debug!("otlp export got {}, retrying", resp.status());
+                attempt += 1;
+                tokio::time::sleep(backoff(attempt)).await;
+            }
+            Err(e) => {
+                tracing::debug!("otlp export request failed: {}, retrying", e);
+                attempt += 1;
+                tokio::time::sleep(backoff(attempt)).await;
+            }
+        }
+    }
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(200 * 2u64.pow(attempt))
+}
+
+fn to_resource_spans(span: Span) -> ResourceSpans {
+    ResourceSpans {
+        resource: Some(Resource {
+            attributes: vec![string_attr("service.name", "llm-fs")],
+            dropped_attributes_count: 0,
+            entity_refs: Vec::new(),
+        }),
+        scope_spans: vec![ScopeSpans {
+            scope: None,
+            spans: vec![to_otlp_span(span)],
+            schema_url: String::new(),
+        }],
+        schema_url: String::new(),
+    }
+}
+
+fn to_otlp_span(span: Span) -> OtlpSpan {
+    let (started_at, ended_at, status) = match &span.status {
+        SpanStatus::Running { started_at } => (*started_at, *started_at, None),
+        SpanStatus::Completed {
+            started_at,
+            ended_at,
+        } => (
+            *started_at,
+            *ended_at,
+            Some(Status {
+                code: StatusCode::Ok as i32,
+                message: String::new(),
+            }),
+        ),
+        SpanStatus::Failed {
+            started_at,
+            ended_at,
+            error,
+        } => (
+            *started_at,
+            *ended_at,
+            Some(Status {
+                code: StatusCode::Error as i32,
+                message: error.clone(),
+            }),
+        ),
+    };
+
+    let mut attributes = Vec::new();
+    if let Some(model) = &span.metadata.model {
+        attributes.push(string_attr("gen_ai.request.model", model));
+    }
+    if let Some(input_tokens) = span.metadata.input_tokens {
+        attributes.push(int_attr("gen_ai.usage.input_tokens", input_tokens as i64));
+    }
+    if let Some(output_tokens) = span.metadata.output_tokens {
+        attributes.push(int_attr("gen_ai.usage.output_tokens", output_tokens as i64));
+    }
+
+    OtlpSpan {
+        // OTLP trace ids are 16 bytes, which our UUID trace ids already are.
+        trace_id: span.trace_id.as_bytes().to_vec(),
+        // OTLP span ids are 8 bytes; take the low half of our 16-byte UUID.
+        span_id: span.id.as_bytes()[8..].to_vec(),
+        parent_span_id: span
+            .parent
+            .map(|p| p.as_bytes()[8..].to_vec())
+            .unwrap_or_default(),
+        name: span.name,
+        kind: SpanKind::Client as i32,
+        start_time_unix_nano: to_unix_nano(started_at),
+        end_time_unix_nano: to_unix_nano(ended_at),
+        attributes,
+        status,
+        ..Default::default()
+    }
+}
+
+fn to_unix_nano(t: DateTime<Utc>) -> u64 {
+    t.timestamp_nanos_opt().unwrap_or(0).max(0) as u64
+}
+
+fn string_attr(key: &str, value: impl Into<String>) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(OtlpValue::StringValue(value.into())),
+        }),
+    }
+}
+
+fn int_attr(key: &str, value: i64) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(OtlpValue::IntValue(value)),
+        }),
+    }
+}
@@ -1,14 +1,20 @@
+use std::sync::Arc;
+
 use api::SharedStore;
+use auth::{KeyStore, Scope};
 use axum::{
+    body::Body,
     extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
     routing::post,
     Json, Router,
 };
+use exporter::OtlpExporter;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use trace::{Span, SpanMetadata};
+use trace::{Span, SpanId, SpanMetadata};
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -16,6 +22,7 @@ struct ProxyState {
     store: SharedStore,
     ollama_url: String,
     client: reqwest::Client,
+    exporter: Option<OtlpExporter>,
 }
 
 // Ollama request/response types
@@ -69,6 +76,19 @@ struct ChatResponse {
     extra: Value,
 }
 
+/// Shape of the final NDJSON line Ollama emits at the end of a streamed
+/// response; every other field on that line is relayed to the client
+/// untouched, so this only needs what we use for span accounting.
+#[derive(Debug, Deserialize)]
+struct StreamDoneLine {
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+}
+
 async fn proxy_generate(
     State(state): State<ProxyState>,
     Json(req): Json<GenerateRequest>,
@@ -92,13 +112,17 @@ async fn proxy_generate(
 
     tracing::info!(%trace_id, %span_id, model = %model, "proxying generate request");
 
-    // Force non-streaming for simplicity
+    let streaming = req.stream == Some(true);
     let mut request_body = serde_json::to_value(&req).unwrap();
-    request_body["stream"] = serde_json::Value::Bool(false);
+    request_body["stream"] = serde_json::Value::Bool(streaming);
 
     let url = format!("{}/api/generate", state.ollama_url);
     let result = state.client.post(&url).json(&request_body).send().await;
 
+    if streaming {
+        return relay_streaming_response(state, span_id, result).await;
+    }
+
     match result {
         Ok(response) => {
             let status = response.status();
@@ -108,12 +132,14 @@ async fn proxy_generate(
                         // Complete span with token counts
                         {
                             let mut store = state.store.write().await;
-                            if let Some(s) = store.get_mut(span_id) {
-                                s.metadata.input_tokens = gen_resp.prompt_eval_count;
-                                s.metadata.output_tokens = gen_resp.eval_count;
-                                s.complete();
-                            }
+                            store.update_metadata(
+                                span_id,
+                                gen_resp.prompt_eval_count,
+                                gen_resp.eval_count,
+                            );
+                            store.complete(span_id);
                         }
+                        export_span(&state, span_id).await;
                         tracing::info!(%span_id, "generate completed");
 
                         // Return full response
@@ -121,18 +147,18 @@ async fn proxy_generate(
                         Ok(Json(resp_json).into_response())
                     }
                     Err(e) => {
-                        fail_span(&state.store, span_id, &format!("Failed to parse response: {}", e)).await;
+                        fail_span(&state, span_id, &format!("Failed to parse response: {}", e)).await;
                         Err(StatusCode::BAD_GATEWAY)
                     }
                 }
             } else {
                 let error_text = response.text().await.unwrap_or_default();
-                fail_span(&state.store, span_id, &format!("Ollama error {}: {}", status, error_text)).await;
+                fail_span(&state, span_id, &format!("Ollama error {}: {}", status, error_text)).await;
                 Err(StatusCode::BAD_GATEWAY)
             }
         }
         Err(e) => {
-            fail_span(&state.store, span_id, &format!("Request failed: {}", e)).await;
+            fail_span(&state, span_id, &format!("Request failed: {}", e)).await;
             Err(StatusCode::BAD_GATEWAY)
         }
     }
@@ -161,13 +187,17 @@ async fn proxy_chat(
 
     tracing::info!(%trace_id, %span_id, model = %model, "proxying chat request");
 
-    // Force non-streaming for simplicity
+    let streaming = req.stream == Some(true);
     let mut request_body = serde_json::to_value(&req).unwrap();
-    request_body["stream"] = serde_json::Value::Bool(false);
+    request_body["stream"] = serde_json::Value::Bool(streaming);
 
     let url = format!("{}/api/chat", state.ollama_url);
     let result = state.client.post(&url).json(&request_body).send().await;
 
+    if streaming {
+        return relay_streaming_response(state, span_id, result).await;
+    }
+
     match result {
         Ok(response) => {
             let status = response.status();
@@ -177,12 +207,14 @@ async fn proxy_chat(
                         // Complete span with token counts
                         {
                             let mut store = state.store.write().await;
-                            if let Some(s) = store.get_mut(span_id) {
-                                s.metadata.input_tokens = chat_resp.prompt_eval_count;
-                                s.metadata.output_tokens = chat_resp.eval_count;
-                                s.complete();
-                            }
+                            store.update_metadata(
+                                span_id,
+                                chat_resp.prompt_eval_count,
+                                chat_resp.eval_count,
+                            );
+                            store.complete(span_id);
                         }
+                        export_span(&state, span_id).await;
                         tracing::info!(%span_id, "chat completed");
 
                         // Return full response
@@ -190,49 +222,253 @@ async fn proxy_chat(
                         Ok(Json(resp_json).into_response())
                     }
                     Err(e) => {
-                        fail_span(&state.store, span_id, &format!("Failed to parse response: {}", e)).await;
+                        fail_span(&state, span_id, &format!("Failed to parse response: {}", e)).await;
                         Err(StatusCode::BAD_GATEWAY)
                     }
                 }
             } else {
                 let error_text = response.text().await.unwrap_or_default();
-                fail_span(&state.store, span_id, &format!("Ollama error {}: {}", status, error_text)).await;
+                fail_span(&state, span_id, &format!("Ollama error {}: {}", status, error_text)).await;
                 Err(StatusCode::BAD_GATEWAY)
             }
         }
         Err(e) => {
-            fail_span(&state.store, span_id, &format!("Request failed: {}", e)).await;
+            fail_span(&state, span_id, &format!("Request failed: {}", e)).await;
             Err(StatusCode::BAD_GATEWAY)
         }
     }
 }
 
-async fn fail_span(store: &SharedStore, span_id: trace::SpanId, error: &str) {
-    let mut w = store.write().await;
-    if let Some(s) = w.get_mut(span_id) {
-        s.fail(error);
+/// Turn an upstream streaming response into a response the client can read
+/// from as it arrives, accounting tokens and closing out the span as the
+/// stream progresses.
+async fn relay_streaming_response(
+    state: ProxyState,
+    span_id: SpanId,
+    result: Result<reqwest::Response, reqwest::Error>,
+) -> Result<Response, StatusCode> {
+    let response = match result {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            fail_span(&state, span_id, &format!("Ollama error {}: {}", status, error_text)).await;
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+        Err(e) => {
+            fail_span(&state, span_id, &format!("Request failed: {}", e)).await;
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    };
+
+    Ok(Response::builder()
+        .header("content-type", "application/x-ndjson")
+        .body(Body::from_stream(relay_stream(state, span_id, response)))
+        .unwrap())
+}
+
+/// Marks `span_id` as failed with "stream interrupted" when dropped, unless
+/// `finish()` was called first. Covers both "the client went away mid-stream"
+/// and "Ollama's final `done: true` line never arrived" without needing
+/// explicit cleanup code on every exit path of the generator below.
+struct StreamCompletionGuard {
+    state: ProxyState,
+    span_id: SpanId,
+    finished: bool,
+}
+
+impl StreamCompletionGuard {
+    fn new(state: ProxyState, span_id: SpanId) -> Self {
+        Self {
+            state,
+            span_id,
+            finished: false,
+        }
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for StreamCompletionGuard {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let state = self.state.clone();
+        let span_id = self.span_id;
+        tokio::spawn(async move {
+            fail_span(&state, span_id, "stream interrupted").await;
+        });
+    }
+}
+
+/// Drain every complete (newline-terminated) line out of `buf` in order,
+/// leaving any trailing partial line for more bytes to complete later.
+fn drain_complete_lines(buf: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    while let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+        lines.push(buf.drain(..=pos).collect());
+    }
+    lines
+}
+
+/// Parse `line` as Ollama's terminal `done: true` NDJSON object, returning
+/// its token counts if this is that line. A trailing newline isn't required
+/// for this to parse, since the NDJSON contract doesn't guarantee the
+/// connection stays open long enough to deliver one.
+fn parse_done_line(line: &[u8]) -> Option<(Option<u64>, Option<u64>)> {
+    let done_line: StreamDoneLine = serde_json::from_slice(line).ok()?;
+    done_line.done.then_some((done_line.prompt_eval_count, done_line.eval_count))
+}
+
+async fn handle_done_line(state: &ProxyState, span_id: SpanId, line: &[u8], guard: &mut StreamCompletionGuard) {
+    let Some((prompt_eval_count, eval_count)) = parse_done_line(line) else {
+        return;
+    };
+    {
+        let mut store = state.store.write().await;
+        store.update_metadata(span_id, prompt_eval_count, eval_count);
+        store.complete(span_id);
+    }
+    export_span(state, span_id).await;
+    guard.finish();
+}
+
+fn relay_stream(
+    state: ProxyState,
+    span_id: SpanId,
+    upstream: reqwest::Response,
+) -> impl futures_util::Stream<Item = Result<bytes::Bytes, std::io::Error>> {
+    async_stream::stream! {
+        let mut guard = StreamCompletionGuard::new(state.clone(), span_id);
+        let mut buf: Vec<u8> = Vec::new();
+        let byte_stream = upstream.bytes_stream();
+        futures_util::pin_mut!(byte_stream);
+
+        while let Some(chunk) = byte_stream.next().await {
+            let bytes = match chunk {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::warn!(%span_id, "stream read error: {}", e);
+                    return;
+                }
+            };
+
+            buf.extend_from_slice(&bytes);
+            for line in drain_complete_lines(&mut buf) {
+                handle_done_line(&state, span_id, &line, &mut guard).await;
+            }
+
+            yield Ok(bytes);
+        }
+
+        // The done:true object isn't guaranteed to be newline-terminated
+        // before the upstream closes the connection; without this, a
+        // trailing partial line sits unparsed in `buf` forever and the
+        // guard's Drop marks an already-delivered response as failed.
+        if !buf.is_empty() {
+            handle_done_line(&state, span_id, &buf, &mut guard).await;
+        }
+    }
+}
+
+async fn fail_span(state: &ProxyState, span_id: trace::SpanId, error: &str) {
+    {
+        let mut w = state.store.write().await;
+        w.fail(span_id, error.to_string());
     }
+    export_span(state, span_id).await;
     tracing::warn!(%span_id, %error, "span failed");
 }
 
-pub fn router(store: SharedStore, ollama_url: String) -> Router {
+/// Hand a just-finished span off to the OTLP exporter, if one is configured.
+async fn export_span(state: &ProxyState, span_id: trace::SpanId) {
+    let Some(exporter) = &state.exporter else {
+        return;
+    };
+    let span = state.store.read().await.get(span_id);
+    if let Some(span) = span {
+        exporter.export(span);
+    }
+}
+
+pub fn router(
+    store: SharedStore,
+    ollama_url: String,
+    exporter: Option<OtlpExporter>,
+    key_store: Option<Arc<KeyStore>>,
+) -> Router {
     let state = ProxyState {
         store,
         ollama_url,
         client: reqwest::Client::new(),
+        exporter,
     };
 
-    Router::new()
+    let router = Router::new()
         .route("/api/generate", post(proxy_generate))
         .route("/api/chat", post(proxy_chat))
-        .with_state(state)
+        .with_state(state);
+
+    match key_store {
+        Some(key_store) => auth::require_scope(router, key_store, Scope::Proxy),
+        None => router,
+    }
 }
 
-pub async fn serve(store: SharedStore, addr: &str, ollama_url: &str) -> std::io::Result<()> {
-    let app = router(store, ollama_url.to_string());
+pub async fn serve(
+    store: SharedStore,
+    addr: &str,
+    ollama_url: &str,
+    exporter: Option<OtlpExporter>,
+    key_store: Option<Arc<KeyStore>>,
+) -> std::io::Result<()> {
+    let app = router(store, ollama_url.to_string(), exporter, key_store);
     let listener = tokio::net::TcpListener::bind(addr).await?;
     tracing::info!("proxy listening on {} -> {}", addr, ollama_url);
     axum::serve(listener, app)
         .await
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drain_complete_lines_splits_on_newline_and_keeps_partial_tail() {
+        let mut buf = b"{\"done\":false}\n{\"partial".to_vec();
+        let lines = drain_complete_lines(&mut buf);
+        assert_eq!(lines, vec![b"{\"done\":false}\n".to_vec()]);
+        assert_eq!(buf, b"{\"partial".to_vec());
+    }
+
+    #[test]
+    fn drain_complete_lines_returns_nothing_without_a_newline() {
+        let mut buf = b"{\"done\":tr".to_vec();
+        assert!(drain_complete_lines(&mut buf).is_empty());
+        assert_eq!(buf, b"{\"done\":tr".to_vec());
+    }
+
+    #[test]
+    fn parse_done_line_ignores_non_terminal_lines() {
+        assert_eq!(parse_done_line(b"{\"done\":false}\n"), None);
+    }
+
+    #[test]
+    fn parse_done_line_extracts_token_counts_from_terminal_line() {
+        let line = br#"{"done":true,"prompt_eval_count":12,"eval_count":34}"#;
+        assert_eq!(parse_done_line(line), Some((Some(12), Some(34))));
+    }
+
+    #[test]
+    fn parse_done_line_tolerates_a_missing_trailing_newline() {
+        // Upstream isn't guaranteed to newline-terminate the final line
+        // before closing the connection; the leftover-buffer flush in
+        // relay_stream depends on this still parsing.
+        let line = br#"{"done":true}"#;
+        assert_eq!(parse_done_line(line), Some((None, None)));
+    }
+}
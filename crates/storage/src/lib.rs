@@ -1,8 +1,18 @@
-use std::collections::HashMap;
-
 use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
 use trace::{Span, SpanId, SpanStatus, TraceId};
 
+mod mem;
+mod sqlite;
+
+pub use mem::MemStore;
+pub use sqlite::SqliteStore;
+
+/// Capacity of each store's `SpanEvent` broadcast channel. Subscribers that
+/// fall this far behind the newest event see a `RecvError::Lagged` on their
+/// next `recv()` rather than observing every event.
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
 /// Filter criteria for querying spans.
 #[derive(Debug, Default, Clone)]
 pub struct SpanFilter {
@@ -13,166 +23,111 @@ pub struct SpanFilter {
     pub name_contains: Option<String>,
 }
 
-/// In-memory span store with dual indexes for fast lookup.
-#[derive(Debug, Default)]
-pub struct SpanStore {
-    spans: HashMap<SpanId, Span>,
-    traces: HashMap<TraceId, Vec<SpanId>>,
+/// Lifecycle event emitted by a `SpanStore` as spans are created and finished.
+///
+/// Consumed by the api crate's `GET /events` SSE stream so dashboards can
+/// watch traces live instead of polling.
+#[derive(Debug, Clone)]
+pub enum SpanEvent {
+    Created(SpanId),
+    Completed(SpanId),
+    Failed(SpanId, String),
 }
 
-impl SpanStore {
-    pub fn new() -> Self {
-        Self::default()
-    }
+/// Storage backend for spans.
+///
+/// Implementations must be safe to share across the `Arc<RwLock<dyn SpanStore>>`
+/// used by the proxy and api crates, so every read returns owned data rather
+/// than references tied to the store's internals.
+pub trait SpanStore: Send + Sync {
+    fn insert(&mut self, span: Span) -> SpanId;
 
-    pub fn insert(&mut self, span: Span) -> SpanId {
-        let id = span.id;
-        let trace_id = span.trace_id;
-        self.spans.insert(id, span);
-        self.traces.entry(trace_id).or_default().push(id);
-        id
-    }
+    fn get(&self, id: SpanId) -> Option<Span>;
 
-    pub fn get(&self, id: SpanId) -> Option<&Span> {
-        self.spans.get(&id)
-    }
+    /// Update the token-count fields on a span without changing its status.
+    fn update_metadata(
+        &mut self,
+        id: SpanId,
+        input_tokens: Option<u64>,
+        output_tokens: Option<u64>,
+    ) -> bool;
 
-    pub fn get_mut(&mut self, id: SpanId) -> Option<&mut Span> {
-        self.spans.get_mut(&id)
-    }
+    fn complete(&mut self, id: SpanId) -> bool;
 
-    pub fn spans_for_trace(&self, trace_id: TraceId) -> &[SpanId] {
-        self.traces
-            .get(&trace_id)
-            .map(|v| v.as_slice())
-            .unwrap_or(&[])
-    }
+    fn fail(&mut self, id: SpanId, error: String) -> bool;
 
-    pub fn trace_ids(&self) -> impl Iterator<Item = &TraceId> {
-        self.traces.keys()
-    }
+    fn spans_for_trace(&self, trace_id: TraceId) -> Vec<SpanId>;
 
-    pub fn all_spans(&self) -> impl Iterator<Item = &Span> {
-        self.spans.values()
-    }
+    fn trace_ids(&self) -> Vec<TraceId>;
 
-    pub fn complete(&mut self, id: SpanId) -> bool {
-        if let Some(span) = self.spans.get_mut(&id) {
-            span.complete();
-            true
-        } else {
-            false
-        }
-    }
+    fn all_spans(&self) -> Vec<Span>;
+
+    fn span_count(&self) -> usize;
+
+    fn trace_count(&self) -> usize;
+
+    /// Delete a single span by ID. Returns true if the span was deleted.
+    fn delete_span(&mut self, id: SpanId) -> bool;
 
-    pub fn fail(&mut self, id: SpanId, error: impl Into<String>) -> bool {
-        if let Some(span) = self.spans.get_mut(&id) {
-            span.fail(error);
-            true
-        } else {
-            false
+    /// Delete all spans for a trace. Returns the number of spans deleted.
+    fn delete_trace(&mut self, trace_id: TraceId) -> usize;
+
+    /// Delete all spans and traces.
+    fn clear(&mut self);
+
+    /// Filter spans by criteria.
+    fn filter_spans(&self, filter: &SpanFilter) -> Vec<Span>;
+
+    /// Subscribe to span lifecycle events (created/completed/failed) as they
+    /// happen. Lagged subscribers miss events rather than blocking the store.
+    fn subscribe(&self) -> broadcast::Receiver<SpanEvent>;
+}
+
+/// Shared `SpanFilter` evaluation, used by `MemStore::filter_spans` and by
+/// the api crate's `GET /events` stream so both paths agree on what a given
+/// filter matches.
+pub fn matches_filter(span: &Span, filter: &SpanFilter) -> bool {
+    if let Some(ref model) = filter.model {
+        match &span.metadata.model {
+            Some(m) if m == model => {}
+            _ => return false,
         }
     }
 
-    pub fn span_count(&self) -> usize {
-        self.spans.len()
+    if let Some(ref status) = filter.status {
+        let span_status = match &span.status {
+            SpanStatus::Running { .. } => "running",
+            SpanStatus::Completed { .. } => "completed",
+            SpanStatus::Failed { .. } => "failed",
+        };
+        if span_status != status {
+            return false;
+        }
     }
 
-    pub fn trace_count(&self) -> usize {
-        self.traces.len()
-    }
+    let started_at = match &span.status {
+        SpanStatus::Running { started_at } => *started_at,
+        SpanStatus::Completed { started_at, .. } => *started_at,
+        SpanStatus::Failed { started_at, .. } => *started_at,
+    };
 
-    /// Delete a single span by ID. Returns true if the span was deleted.
-    pub fn delete_span(&mut self, id: SpanId) -> bool {
-        if let Some(span) = self.spans.remove(&id) {
-            // Remove from trace index
-            if let Some(span_ids) = self.traces.get_mut(&span.trace_id) {
-                span_ids.retain(|&sid| sid != id);
-                // Clean up empty trace entry
-                if span_ids.is_empty() {
-                    self.traces.remove(&span.trace_id);
-                }
-            }
-            true
-        } else {
-            false
+    if let Some(since) = filter.since {
+        if started_at < since {
+            return false;
         }
     }
 
-    /// Delete all spans for a trace. Returns the number of spans deleted.
-    pub fn delete_trace(&mut self, trace_id: TraceId) -> usize {
-        if let Some(span_ids) = self.traces.remove(&trace_id) {
-            let count = span_ids.len();
-            for id in span_ids {
-                self.spans.remove(&id);
-            }
-            count
-        } else {
-            0
+    if let Some(until) = filter.until {
+        if started_at > until {
+            return false;
         }
     }
 
-    /// Delete all spans and traces.
-    pub fn clear(&mut self) {
-        self.spans.clear();
-        self.traces.clear();
+    if let Some(ref name_contains) = filter.name_contains {
+        if !span.name.contains(name_contains) {
+            return false;
+        }
     }
 
-    /// Filter spans by criteria.
-    pub fn filter_spans(&self, filter: &SpanFilter) -> Vec<&Span> {
-        self.spans
-            .values()
-            .filter(|span| {
-                // Filter by model
-                if let Some(ref model) = filter.model {
-                    match &span.metadata.model {
-                        Some(m) if m == model => {}
-                        _ => return false,
-                    }
-                }
-
-                // Filter by status
-                if let Some(ref status) = filter.status {
-                    let span_status = match &span.status {
-                        SpanStatus::Running { .. } => "running",
-                        SpanStatus::Completed { .. } => "completed",
-                        SpanStatus::Failed { .. } => "failed",
-                    };
-                    if span_status != status {
-                        return false;
-                    }
-                }
-
-                // Get started_at from span status
-                let started_at = match &span.status {
-                    SpanStatus::Running { started_at } => *started_at,
-                    SpanStatus::Completed { started_at, .. } => *started_at,
-                    SpanStatus::Failed { started_at, .. } => *started_at,
-                };
-
-                // Filter by since
-                if let Some(since) = filter.since {
-                    if started_at < since {
-                        return false;
-                    }
-                }
-
-                // Filter by until
-                if let Some(until) = filter.until {
-                    if started_at > until {
-                        return false;
-                    }
-                }
-
-                // Filter by name contains
-                if let Some(ref name_contains) = filter.name_contains {
-                    if !span.name.contains(name_contains) {
-                        return false;
-                    }
-                }
-
-                true
-            })
-            .collect()
-    }
+    true
 }
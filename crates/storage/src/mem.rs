@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use tokio::sync::broadcast;
+use trace::{Span, SpanId, SpanStatus, TraceId};
+
+use crate::{SpanEvent, SpanFilter, SpanStore, EVENT_CHANNEL_CAPACITY};
+
+/// In-memory span store with dual indexes for fast lookup.
+///
+/// Nothing here is persisted; everything is lost when the process exits.
+/// Use `SqliteStore` when spans need to survive a restart.
+#[derive(Debug)]
+pub struct MemStore {
+    spans: HashMap<SpanId, Span>,
+    traces: HashMap<TraceId, Vec<SpanId>>,
+    events: broadcast::Sender<SpanEvent>,
+}
+
+impl Default for MemStore {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            spans: HashMap::new(),
+            traces: HashMap::new(),
+            events,
+        }
+    }
+}
+
+impl MemStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SpanStore for MemStore {
+    fn insert(&mut self, span: Span) -> SpanId {
+        let id = span.id;
+        let trace_id = span.trace_id;
+        self.spans.insert(id, span);
+        self.traces.entry(trace_id).or_default().push(id);
+        let _ = self.events.send(SpanEvent::Created(id));
+        id
+    }
+
+    fn get(&self, id: SpanId) -> Option<Span> {
+        self.spans.get(&id).cloned()
+    }
+
+    fn update_metadata(
+        &mut self,
+        id: SpanId,
+        input_tokens: Option<u64>,
+        output_tokens: Option<u64>,
+    ) -> bool {
+        if let Some(span) = self.spans.get_mut(&id) {
+            span.metadata.input_tokens = input_tokens;
+            span.metadata.output_tokens = output_tokens;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn complete(&mut self, id: SpanId) -> bool {
+        match self.spans.get_mut(&id) {
+            Some(span) if matches!(span.status, SpanStatus::Running { .. }) => {
+                span.complete();
+                let _ = self.events.send(SpanEvent::Completed(id));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn fail(&mut self, id: SpanId, error: String) -> bool {
+        match self.spans.get_mut(&id) {
+            Some(span) if matches!(span.status, SpanStatus::Running { .. }) => {
+                span.fail(error.clone());
+                let _ = self.events.send(SpanEvent::Failed(id, error));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn spans_for_trace(&self, trace_id: TraceId) -> Vec<SpanId> {
+        self.traces.get(&trace_id).cloned().unwrap_or_default()
+    }
+
+    fn trace_ids(&self) -> Vec<TraceId> {
+        self.traces.keys().copied().collect()
+    }
+
+    fn all_spans(&self) -> Vec<Span> {
+        self.spans.values().cloned().collect()
+    }
+
+    fn span_count(&self) -> usize {
+        self.spans.len()
+    }
+
+    fn trace_count(&self) -> usize {
+        self.traces.len()
+    }
+
+    fn delete_span(&mut self, id: SpanId) -> bool {
+        if let Some(span) = self.spans.remove(&id) {
+            if let Some(span_ids) = self.traces.get_mut(&span.trace_id) {
+                span_ids.retain(|&sid| sid != id);
+                if span_ids.is_empty() {
+                    self.traces.remove(&span.trace_id);
+                }
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    fn delete_trace(&mut self, trace_id: TraceId) -> usize {
+        if let Some(span_ids) = self.traces.remove(&trace_id) {
+            let count = span_ids.len();
+            for id in span_ids {
+                self.spans.remove(&id);
+            }
+            count
+        } else {
+            0
+        }
+    }
+
+    fn clear(&mut self) {
+        self.spans.clear();
+        self.traces.clear();
+    }
+
+    fn filter_spans(&self, filter: &SpanFilter) -> Vec<Span> {
+        self.spans
+            .values()
+            .filter(|span| crate::matches_filter(span, filter))
+            .cloned()
+            .collect()
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<SpanEvent> {
+        self.events.subscribe()
+    }
+}
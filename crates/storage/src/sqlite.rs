@@ -0,0 +1,381 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, Row};
+use tokio::sync::broadcast;
+use trace::{Span, SpanId, SpanMetadata, SpanStatus, TraceId};
+
+use crate::{SpanEvent, SpanFilter, SpanStore, EVENT_CHANNEL_CAPACITY};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS spans (
+    span_id       TEXT PRIMARY KEY,
+    trace_id      TEXT NOT NULL,
+    parent_id     TEXT,
+    name          TEXT NOT NULL,
+    model         TEXT,
+    status        TEXT NOT NULL,
+    started_at    TEXT NOT NULL,
+    ended_at      TEXT,
+    input_tokens  INTEGER,
+    output_tokens INTEGER,
+    error         TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_spans_trace_id ON spans(trace_id);
+CREATE INDEX IF NOT EXISTS idx_spans_model_started_at ON spans(model, started_at);
+";
+
+/// SQLite-backed span store.
+///
+/// Every mutation is written through to the database immediately, and
+/// `open` loads the schema (not the rows) eagerly so both running and
+/// already-closed spans are visible to queries as soon as the daemon comes
+/// back up, without a separate explicit reload step.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    events: broadcast::Sender<SpanEvent>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Ok(Self {
+            conn: Mutex::new(conn),
+            events,
+        })
+    }
+
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(SCHEMA)?;
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Ok(Self {
+            conn: Mutex::new(conn),
+            events,
+        })
+    }
+}
+
+impl SpanStore for SqliteStore {
+    fn insert(&mut self, span: Span) -> SpanId {
+        let id = span.id;
+        {
+            let conn = self.conn.lock().unwrap();
+            let (status, started_at, ended_at, error) = status_columns(&span.status);
+            if let Err(e) = conn.execute(
+                "INSERT INTO spans (span_id, trace_id, parent_id, name, model, status, started_at, ended_at, input_tokens, output_tokens, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    span.id.to_string(),
+                    span.trace_id.to_string(),
+                    span.parent.map(|p| p.to_string()),
+                    span.name,
+                    span.metadata.model,
+                    status,
+                    started_at.to_rfc3339(),
+                    ended_at.map(|t| t.to_rfc3339()),
+                    span.metadata.input_tokens.map(|v| v as i64),
+                    span.metadata.output_tokens.map(|v| v as i64),
+                    error,
+                ],
+            ) {
+                tracing::error!(span_id = %id, "failed to insert span: {}", e);
+            }
+        }
+        let _ = self.events.send(SpanEvent::Created(id));
+        id
+    }
+
+    fn get(&self, id: SpanId) -> Option<Span> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT span_id, trace_id, parent_id, name, model, status, started_at, ended_at, input_tokens, output_tokens, error
+             FROM spans WHERE span_id = ?1",
+            params![id.to_string()],
+            row_to_span,
+        )
+        .ok()
+    }
+
+    fn update_metadata(
+        &mut self,
+        id: SpanId,
+        input_tokens: Option<u64>,
+        output_tokens: Option<u64>,
+    ) -> bool {
+        let conn = self.conn.lock().unwrap();
+        match conn.execute(
+            "UPDATE spans SET input_tokens = ?1, output_tokens = ?2 WHERE span_id = ?3",
+            params![
+                input_tokens.map(|v| v as i64),
+                output_tokens.map(|v| v as i64),
+                id.to_string()
+            ],
+        ) {
+            Ok(changed) => changed > 0,
+            Err(e) => {
+                tracing::error!(span_id = %id, "failed to update span metadata: {}", e);
+                false
+            }
+        }
+    }
+
+    fn complete(&mut self, id: SpanId) -> bool {
+        let changed = {
+            let conn = self.conn.lock().unwrap();
+            let now = Utc::now().to_rfc3339();
+            match conn.execute(
+                "UPDATE spans SET status = 'completed', ended_at = ?1 WHERE span_id = ?2 AND status = 'running'",
+                params![now, id.to_string()],
+            ) {
+                Ok(changed) => changed > 0,
+                Err(e) => {
+                    tracing::error!(span_id = %id, "failed to complete span: {}", e);
+                    false
+                }
+            }
+        };
+        if changed {
+            let _ = self.events.send(SpanEvent::Completed(id));
+        }
+        changed
+    }
+
+    fn fail(&mut self, id: SpanId, error: String) -> bool {
+        let changed = {
+            let conn = self.conn.lock().unwrap();
+            let now = Utc::now().to_rfc3339();
+            match conn.execute(
+                "UPDATE spans SET status = 'failed', ended_at = ?1, error = ?2 WHERE span_id = ?3 AND status = 'running'",
+                params![now, error, id.to_string()],
+            ) {
+                Ok(changed) => changed > 0,
+                Err(e) => {
+                    tracing::error!(span_id = %id, "failed to fail span: {}", e);
+                    false
+                }
+            }
+        };
+        if changed {
+            let _ = self.events.send(SpanEvent::Failed(id, error));
+        }
+        changed
+    }
+
+    fn spans_for_trace(&self, trace_id: TraceId) -> Vec<SpanId> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn
+            .prepare("SELECT span_id FROM spans WHERE trace_id = ?1")
+            .and_then(|mut stmt| {
+                let ids = stmt
+                    .query_map(params![trace_id.to_string()], |row| {
+                        parse_uuid(row.get::<_, String>(0)?)
+                    })?
+                    .filter_map(Result::ok)
+                    .collect::<Vec<_>>();
+                Ok(ids)
+            });
+        result.unwrap_or_else(|e| {
+            tracing::error!(%trace_id, "failed to query spans_for_trace: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn trace_ids(&self) -> Vec<TraceId> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.prepare("SELECT DISTINCT trace_id FROM spans").and_then(|mut stmt| {
+            let ids = stmt
+                .query_map([], |row| parse_uuid(row.get::<_, String>(0)?))?
+                .filter_map(Result::ok)
+                .collect::<Vec<_>>();
+            Ok(ids)
+        });
+        result.unwrap_or_else(|e| {
+            tracing::error!("failed to query trace_ids: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn all_spans(&self) -> Vec<Span> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn
+            .prepare(
+                "SELECT span_id, trace_id, parent_id, name, model, status, started_at, ended_at, input_tokens, output_tokens, error FROM spans",
+            )
+            .and_then(|mut stmt| {
+                let spans = stmt.query_map([], row_to_span)?.filter_map(Result::ok).collect::<Vec<_>>();
+                Ok(spans)
+            });
+        result.unwrap_or_else(|e| {
+            tracing::error!("failed to query all_spans: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn span_count(&self) -> usize {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM spans", [], |row| row.get(0))
+            .unwrap_or(0)
+    }
+
+    fn trace_count(&self) -> usize {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(DISTINCT trace_id) FROM spans", [], |row| {
+            row.get(0)
+        })
+        .unwrap_or(0)
+    }
+
+    fn delete_span(&mut self, id: SpanId) -> bool {
+        let conn = self.conn.lock().unwrap();
+        match conn.execute("DELETE FROM spans WHERE span_id = ?1", params![id.to_string()]) {
+            Ok(changed) => changed > 0,
+            Err(e) => {
+                tracing::error!(span_id = %id, "failed to delete span: {}", e);
+                false
+            }
+        }
+    }
+
+    fn delete_trace(&mut self, trace_id: TraceId) -> usize {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM spans WHERE trace_id = ?1",
+            params![trace_id.to_string()],
+        )
+        .unwrap_or_else(|e| {
+            tracing::error!(%trace_id, "failed to delete trace: {}", e);
+            0
+        })
+    }
+
+    fn clear(&mut self) {
+        let conn = self.conn.lock().unwrap();
+        if let Err(e) = conn.execute("DELETE FROM spans", []) {
+            tracing::error!("failed to clear spans: {}", e);
+        }
+    }
+
+    fn filter_spans(&self, filter: &SpanFilter) -> Vec<Span> {
+        let mut clauses = Vec::new();
+        let mut values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(ref model) = filter.model {
+            clauses.push(format!("model = ?{}", values.len() + 1));
+            values.push(Box::new(model.clone()));
+        }
+        if let Some(ref status) = filter.status {
+            clauses.push(format!("status = ?{}", values.len() + 1));
+            values.push(Box::new(status.clone()));
+        }
+        if let Some(since) = filter.since {
+            clauses.push(format!("started_at >= ?{}", values.len() + 1));
+            values.push(Box::new(since.to_rfc3339()));
+        }
+        if let Some(until) = filter.until {
+            clauses.push(format!("started_at <= ?{}", values.len() + 1));
+            values.push(Box::new(until.to_rfc3339()));
+        }
+        if let Some(ref name_contains) = filter.name_contains {
+            // instr() is a case-sensitive literal substring match, the same
+            // as `str::contains` in `matches_filter`; LIKE would ignore ASCII case.
+            clauses.push(format!("instr(name, ?{}) > 0", values.len() + 1));
+            values.push(Box::new(name_contains.clone()));
+        }
+
+        let mut query = "SELECT span_id, trace_id, parent_id, name, model, status, started_at, ended_at, input_tokens, output_tokens, error FROM spans".to_string();
+        if !clauses.is_empty() {
+            query.push_str(" WHERE ");
+            query.push_str(&clauses.join(" AND "));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let result = conn.prepare(&query).and_then(|mut stmt| {
+            let param_refs: Vec<&dyn rusqlite::ToSql> = values.iter().map(|v| v.as_ref()).collect();
+            let spans = stmt
+                .query_map(param_refs.as_slice(), row_to_span)?
+                .filter_map(Result::ok)
+                .collect::<Vec<_>>();
+            Ok(spans)
+        });
+        result.unwrap_or_else(|e| {
+            tracing::error!("failed to query filter_spans: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<SpanEvent> {
+        self.events.subscribe()
+    }
+}
+
+fn status_columns(status: &SpanStatus) -> (&'static str, DateTime<Utc>, Option<DateTime<Utc>>, Option<String>) {
+    match status {
+        SpanStatus::Running { started_at } => ("running", *started_at, None, None),
+        SpanStatus::Completed { started_at, ended_at } => {
+            ("completed", *started_at, Some(*ended_at), None)
+        }
+        SpanStatus::Failed {
+            started_at,
+            ended_at,
+            error,
+        } => ("failed", *started_at, Some(*ended_at), Some(error.clone())),
+    }
+}
+
+fn parse_uuid(raw: String) -> rusqlite::Result<uuid::Uuid> {
+    raw.parse()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn row_to_span(row: &Row<'_>) -> rusqlite::Result<Span> {
+    let id: String = row.get(0)?;
+    let trace_id: String = row.get(1)?;
+    let parent_id: Option<String> = row.get(2)?;
+    let name: String = row.get(3)?;
+    let model: Option<String> = row.get(4)?;
+    let status: String = row.get(5)?;
+    let started_at: String = row.get(6)?;
+    let ended_at: Option<String> = row.get(7)?;
+    let input_tokens: Option<i64> = row.get(8)?;
+    let output_tokens: Option<i64> = row.get(9)?;
+    let error: Option<String> = row.get(10)?;
+
+    let started_at = parse_timestamp(&started_at)?;
+    let ended_at = ended_at.as_deref().map(parse_timestamp).transpose()?;
+
+    let status = match status.as_str() {
+        "running" => SpanStatus::Running { started_at },
+        "completed" => SpanStatus::Completed {
+            started_at,
+            ended_at: ended_at.unwrap_or(started_at),
+        },
+        _ => SpanStatus::Failed {
+            started_at,
+            ended_at: ended_at.unwrap_or(started_at),
+            error: error.unwrap_or_default(),
+        },
+    };
+
+    Ok(Span {
+        id: parse_uuid(id)?,
+        trace_id: parse_uuid(trace_id)?,
+        parent: parent_id.map(parse_uuid).transpose()?,
+        name,
+        status,
+        metadata: SpanMetadata {
+            model,
+            input_tokens: input_tokens.map(|v| v as u64),
+            output_tokens: output_tokens.map(|v| v as u64),
+        },
+    })
+}
+
+fn parse_timestamp(raw: &str) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}